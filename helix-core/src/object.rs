@@ -1,21 +1,114 @@
-use crate::{movement::Direction, Range, RopeSlice, Selection, Syntax};
+use crate::{movement::Direction, Range, RopeSlice, Selection, Syntax, Tendril, Transaction};
 use tree_sitter::{Node, Tree};
 
-pub fn expand_selection(syntax: &Syntax, text: RopeSlice, selection: Selection) -> Selection {
-    select_node_impl(syntax, text, selection, |mut node, from, to| {
-        while node.start_byte() == from && node.end_byte() == to {
+/// Tracks the selections `expand_selection` passed through, per view, so a
+/// matching `shrink_selection` retraces the exact path back down instead of
+/// always descending to the first child. Each entry is tagged with the
+/// document revision it was recorded at (`Document::get_current_revision`)
+/// and the selection `expand_selection` produced, so the stack is cleared
+/// not just by an edit but by *any* foreign change to the selection (a
+/// cursor motion, a click, another command) between the expand and the
+/// shrink, rather than relying on the caller to remember to clear it.
+#[derive(Debug, Default, Clone)]
+pub struct SelectionHistory {
+    stack: Vec<(usize, Selection, Selection)>,
+}
+
+impl SelectionHistory {
+    pub fn clear(&mut self) {
+        self.stack.clear();
+    }
+}
+
+pub fn expand_selection(
+    syntax: &Syntax,
+    text: RopeSlice,
+    selection: Selection,
+    revision: usize,
+    history: &mut SelectionHistory,
+) -> Selection {
+    // an edit, or any other command that moved the selection, since the
+    // last push means the stack no longer lines up with the current
+    // selection (or the document it was computed against); drop it rather
+    // than let a later shrink restore something stale
+    if history
+        .stack
+        .last()
+        .is_some_and(|(rev, _, expanded)| *rev != revision || *expanded != selection)
+    {
+        history.stack.clear();
+    }
+
+    let expanded = select_node_impl(syntax, text, selection.clone(), |mut node, from, to| {
+        // keep climbing past anonymous nodes (punctuation, keywords) and
+        // past the range's own span, so we land on the smallest *named*
+        // ancestor that strictly contains the current range
+        while !node.is_named() || (node.start_byte() == from && node.end_byte() == to) {
             node = node.parent()?;
         }
         Some(node)
-    })
+    });
+
+    if expanded != selection {
+        history.stack.push((revision, selection, expanded.clone()));
+    }
+
+    expanded
 }
 
-pub fn shrink_selection(syntax: &Syntax, text: RopeSlice, selection: Selection) -> Selection {
+/// The `revision`/`history` pairing with [`expand_selection`] guarantees a
+/// sequence of expands followed by equal shrinks round-trips precisely: a
+/// pop is only honored when `revision` still matches the one recorded at
+/// push time *and* `selection` still matches the selection that expand
+/// produced, so an edit, or any other command that moved the selection in
+/// between, falls back to child-descent instead of restoring a now-stale
+/// selection.
+pub fn shrink_selection(
+    syntax: &Syntax,
+    text: RopeSlice,
+    selection: Selection,
+    revision: usize,
+    history: &mut SelectionHistory,
+) -> Selection {
+    // retrace the exact selection the matching expand started from, rather
+    // than just descending to the first child, which can land somewhere
+    // different than where the user expanded from - but only if nothing
+    // has edited the document, or moved the selection some other way,
+    // since the matching expand
+    if let Some((pushed_revision, previous, expanded)) = history.stack.pop() {
+        if pushed_revision == revision && expanded == selection {
+            return previous;
+        }
+        history.stack.clear();
+    }
+
     select_node_impl(syntax, text, selection, |descendant, _from, _to| {
         descendant.child(0).or(Some(descendant))
     })
 }
 
+/// Selects the nearest ancestor of each range whose `node.kind()` is one of
+/// `kinds` (e.g. `function_item`, `struct_item`, `impl_item`), without
+/// relying on a textobject query. Ranges with no matching ancestor are left
+/// unchanged.
+pub fn select_enclosing_kind(
+    syntax: &Syntax,
+    text: RopeSlice,
+    selection: Selection,
+    kinds: &[&str],
+) -> Selection {
+    select_node_impl(syntax, text, selection, |node, _from, _to| {
+        let mut node = Some(node);
+        while let Some(n) = node {
+            if kinds.contains(&n.kind()) {
+                return Some(n);
+            }
+            node = n.parent();
+        }
+        None
+    })
+}
+
 pub fn select_sibling<F>(
     syntax: &Syntax,
     text: RopeSlice,
@@ -30,9 +123,177 @@ where
     })
 }
 
-fn find_parent_with_more_children(mut node: Node) -> Option<Node> {
+/// Swaps each range's enclosing named node with its next named sibling in
+/// the tree, moving the range along with the relocated node (e.g. in
+/// `bar(a, b, c)`, invoking this with the cursor on `a` produces
+/// `bar(b, a, c)` with the selection left on `a`). The separator between
+/// the two siblings (commas, whitespace, ...) is left untouched; only the
+/// sibling spans themselves are swapped. Ranges with no sibling in the
+/// requested direction are left unchanged, mirroring `select_all_siblings`'s
+/// "can't pick any more siblings" case.
+pub fn transpose_node_next(
+    syntax: &Syntax,
+    text: RopeSlice,
+    selection: Selection,
+) -> (Transaction, Selection) {
+    transpose_node_impl(syntax, text, selection, |node| node.next_named_sibling())
+}
+
+/// Same as [`transpose_node_next`] but swaps with the previous named
+/// sibling.
+pub fn transpose_node_prev(
+    syntax: &Syntax,
+    text: RopeSlice,
+    selection: Selection,
+) -> (Transaction, Selection) {
+    transpose_node_impl(syntax, text, selection, |node| node.prev_named_sibling())
+}
+
+fn transpose_node_impl<F>(
+    syntax: &Syntax,
+    text: RopeSlice,
+    selection: Selection,
+    sibling_fn: F,
+) -> (Transaction, Selection)
+where
+    F: Fn(&Node) -> Option<Node>,
+{
+    let tree = syntax.tree();
+
+    let mut changes: Vec<(usize, usize, Option<Tendril>)> = Vec::new();
+    // for each range, the old char span that will end up holding the
+    // range's own node text once the swap lands, and that text's length in
+    // chars; `None` for ranges left unchanged
+    let mut targets: Vec<Option<(usize, usize)>> = Vec::new();
+    // char spans already claimed by another range's swap in this same
+    // invocation, so two cursors that resolve to the same (or overlapping)
+    // sibling pair - e.g. cursors on both `a` and `b` in `bar(a, b, c)` -
+    // don't each emit a conflicting change over the same text
+    let mut claimed_spans: Vec<(usize, usize)> = Vec::new();
+
+    for range in selection.ranges() {
+        let from = text.char_to_byte(range.from());
+        let to = text.char_to_byte(range.to());
+
+        let swap = tree
+            .root_node()
+            .descendant_for_byte_range(from, to)
+            .and_then(|node| {
+                let mut node = node;
+                while !node.is_named() {
+                    node = node.parent()?;
+                }
+                Some(node)
+            })
+            .and_then(|node| sibling_fn(&node).map(|sibling| (node, sibling)));
+
+        let Some((node, sibling)) = swap else {
+            targets.push(None);
+            continue;
+        };
+
+        let node_text = Tendril::from(text.byte_slice(node.start_byte()..node.end_byte()).to_string());
+        let sibling_text =
+            Tendril::from(text.byte_slice(sibling.start_byte()..sibling.end_byte()).to_string());
+
+        let (first_start, first_end) = (
+            text.byte_to_char(node.start_byte().min(sibling.start_byte())),
+            text.byte_to_char(node.end_byte().min(sibling.end_byte())),
+        );
+        let (second_start, second_end) = (
+            text.byte_to_char(node.start_byte().max(sibling.start_byte())),
+            text.byte_to_char(node.end_byte().max(sibling.end_byte())),
+        );
+
+        // another range already claimed (part of) this swap's span - e.g.
+        // a second cursor that resolved to the same sibling pair - so leave
+        // this range unchanged rather than emitting an overlapping change
+        if claimed_spans
+            .iter()
+            .any(|&(start, end)| first_start < end && start < second_end)
+        {
+            targets.push(None);
+            continue;
+        }
+        claimed_spans.push((first_start, second_end));
+
+        let (first_text, second_text) = if node.start_byte() < sibling.start_byte() {
+            (sibling_text, node_text.clone())
+        } else {
+            (node_text.clone(), sibling_text)
+        };
+
+        // whichever of the two slots ends up holding `node`'s own text is
+        // where the selection should land
+        let target_start = if node.start_byte() < sibling.start_byte() {
+            second_start
+        } else {
+            first_start
+        };
+        targets.push(Some((target_start, node_text.chars().count())));
+
+        changes.push((first_start, first_end, Some(first_text)));
+        changes.push((second_start, second_end, Some(second_text)));
+    }
+
+    changes.sort_by_key(|(start, _, _)| *start);
+
+    let deltas: Vec<(usize, isize)> = changes
+        .iter()
+        .map(|(start, end, tendril)| {
+            let new_len = tendril.as_ref().map(|t| t.chars().count()).unwrap_or(0);
+            (*start, new_len as isize - (end - start) as isize)
+        })
+        .collect();
+
+    let delta_before = |pos: usize| -> isize {
+        deltas
+            .iter()
+            .filter(|(start, _)| *start < pos)
+            .map(|(_, delta)| delta)
+            .sum()
+    };
+
+    let mut targets = targets.into_iter();
+    let new_selection = selection.clone().transform(|range| match targets.next().flatten() {
+        Some((old_start, moved_len)) => {
+            let new_start = (old_start as isize + delta_before(old_start)) as usize;
+            let new_end = new_start + moved_len;
+
+            if range.head < range.anchor {
+                Range::new(new_end, new_start)
+            } else {
+                Range::new(new_start, new_end)
+            }
+        }
+        None => range,
+    });
+
+    (Transaction::change(text, changes.into_iter()), new_selection)
+}
+
+/// Which of a node's children `select_all_siblings`/`select_all_children`
+/// consider: only the named, semantic children (list items, arguments, ...)
+/// or every child including anonymous delimiter/keyword nodes (`{`, `}`,
+/// `else`, ...). Mirrors the `syntax-selection.include-anonymous` option.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NodeFilter {
+    Named,
+    All,
+}
+
+impl NodeFilter {
+    fn child_count(self, node: &Node) -> usize {
+        match self {
+            NodeFilter::Named => node.named_child_count(),
+            NodeFilter::All => node.child_count(),
+        }
+    }
+}
+
+fn find_parent_with_more_children(mut node: Node, filter: NodeFilter) -> Option<Node> {
     while let Some(parent) = node.parent() {
-        if parent.child_count() > 1 {
+        if filter.child_count(&parent) > 1 {
             return Some(parent);
         }
 
@@ -42,7 +303,12 @@ fn find_parent_with_more_children(mut node: Node) -> Option<Node> {
     None
 }
 
-pub fn select_all_siblings(tree: &Tree, text: RopeSlice, selection: Selection) -> Selection {
+pub fn select_all_siblings(
+    tree: &Tree,
+    text: RopeSlice,
+    selection: Selection,
+    filter: NodeFilter,
+) -> Selection {
     let root_node = &tree.root_node();
 
     selection.transform_iter(|range| {
@@ -51,13 +317,18 @@ pub fn select_all_siblings(tree: &Tree, text: RopeSlice, selection: Selection) -
 
         root_node
             .descendant_for_byte_range(from, to)
-            .and_then(find_parent_with_more_children)
-            .and_then(|parent| select_children(parent, text, range.direction()))
+            .and_then(|node| find_parent_with_more_children(node, filter))
+            .and_then(|parent| select_children(parent, text, range.direction(), filter))
             .unwrap_or_else(|| vec![range].into_iter())
     })
 }
 
-pub fn select_all_children(tree: &Tree, text: RopeSlice, selection: Selection) -> Selection {
+pub fn select_all_children(
+    tree: &Tree,
+    text: RopeSlice,
+    selection: Selection,
+    filter: NodeFilter,
+) -> Selection {
     let root_node = &tree.root_node();
 
     selection.transform_iter(|range| {
@@ -66,31 +337,40 @@ pub fn select_all_children(tree: &Tree, text: RopeSlice, selection: Selection) -
 
         root_node
             .descendant_for_byte_range(from, to)
-            .and_then(|parent| select_children(parent, text, range.direction()))
+            .and_then(|parent| select_children(parent, text, range.direction(), filter))
             .unwrap_or_else(|| vec![range].into_iter())
     })
 }
 
+fn child_range(child: Node, text: RopeSlice, direction: Direction) -> Range {
+    let from = text.byte_to_char(child.start_byte());
+    let to = text.byte_to_char(child.end_byte());
+
+    if direction == Direction::Backward {
+        Range::new(to, from)
+    } else {
+        Range::new(from, to)
+    }
+}
+
 fn select_children(
     node: Node,
     text: RopeSlice,
     direction: Direction,
+    filter: NodeFilter,
 ) -> Option<<Vec<Range> as std::iter::IntoIterator>::IntoIter> {
     let mut cursor = node.walk();
 
-    let children = node
-        .named_children(&mut cursor)
-        .map(|child| {
-            let from = text.byte_to_char(child.start_byte());
-            let to = text.byte_to_char(child.end_byte());
-
-            if direction == Direction::Backward {
-                Range::new(to, from)
-            } else {
-                Range::new(from, to)
-            }
-        })
-        .collect::<Vec<_>>();
+    let children = match filter {
+        NodeFilter::Named => node
+            .named_children(&mut cursor)
+            .map(|child| child_range(child, text, direction))
+            .collect::<Vec<_>>(),
+        NodeFilter::All => node
+            .children(&mut cursor)
+            .map(|child| child_range(child, text, direction))
+            .collect::<Vec<_>>(),
+    };
 
     if !children.is_empty() {
         Some(children.into_iter())
@@ -16,11 +16,11 @@ use helix_core::{
         ensure_grapheme_boundary_next_byte, next_grapheme_boundary, prev_grapheme_boundary,
     },
     movement::Direction,
-    syntax::{self, HighlightEvent, RopeProvider},
+    syntax::{self, Highlight, HighlightEvent, RopeProvider},
     text_annotations::TextAnnotations,
     tree_sitter::{QueryCursor, QueryMatch},
     unicode::width::UnicodeWidthStr,
-    visual_offset_from_block, Change, Position, Range, Selection, Transaction,
+    visual_offset_from_block, Change, Position, Range, RopeSlice, Selection, Transaction,
 };
 use helix_view::{
     document::{Mode, SavePoint, SCRATCH_BUFFER_NAME},
@@ -67,6 +67,25 @@ impl PartialOrd for StickyNode {
     }
 }
 
+/// How many lines the anchor can move between frames before
+/// `calculate_sticky_nodes` gives up on an incremental update and rescans
+/// from byte 0. Keeps ordinary scrolling cheap while still bounding the
+/// work a big jump (a search, `G`, ...) triggers.
+const STICKY_CONTEXT_INCREMENTAL_SCROLL_THRESHOLD: usize = 8;
+
+/// Cached sticky-context state, reused across frames so a small scroll
+/// only re-queries the bytes the anchor moved through instead of
+/// rescanning the whole document every time.
+#[derive(Debug, Clone, Default)]
+struct StickyContextCache {
+    nodes: Vec<StickyNode>,
+    anchor_line: usize,
+    last_scan_byte: usize,
+    /// Document revision the cache was built from; a mismatch means the
+    /// document was edited since, invalidating incremental updates.
+    revision: usize,
+}
+
 pub struct EditorView {
     pub keymaps: Keymaps,
     on_next_key: Option<OnKeyCallback>,
@@ -75,6 +94,35 @@ pub struct EditorView {
     pub(crate) completion: Option<Completion>,
     spinners: ProgressSpinners,
     sticky_nodes: Option<Vec<StickyNode>>,
+    sticky_context_cache: StickyContextCache,
+    /// Screen column range occupied by each rendered bufferline tab, in
+    /// left-to-right render order. Kept so a follow-up can hit-test a click
+    /// against a tab without recomputing the bufferline layout.
+    bufferline_areas: Vec<(std::ops::Range<u16>, helix_view::DocumentId)>,
+    /// Horizontal scroll offset (in columns) applied to the bufferline so
+    /// the active tab stays visible when there are more tabs than fit.
+    bufferline_offset: u16,
+    /// Doc lines whose sticky context row has been collapsed (right-clicked)
+    /// and should be hidden from the rendered context stack.
+    collapsed_context_lines: BTreeSet<usize>,
+    /// The most recent change-producing action, used to implement `.`
+    /// dot-repeat. Updated both by plain normal-mode edits and whenever an
+    /// insert session commits, so it always reflects whatever happened most
+    /// recently, superseding `last_insert` when set.
+    last_change: Option<Replay>,
+    /// Keys consumed by the in-progress normal-mode command (operator,
+    /// motion and count), accumulated until the keymap fully resolves.
+    change_keys: Vec<KeyEvent>,
+    /// Line a left-button press in the gutter landed on, kept so a
+    /// following `Drag(Left)` can extend a linewise selection from it and
+    /// release can tell a plain click (toggle the breakpoint) from a drag
+    /// (keep the selection).
+    gutter_drag_anchor: Option<usize>,
+    /// Counts stashed by an operator/prefix key that left other keys
+    /// pending, so the motion that eventually completes the sequence can
+    /// have its own count multiplied into the outer one (`2d3w` deletes
+    /// `2 * 3` words). Empty whenever no sequence is in progress.
+    pending_counts: Vec<NonZeroUsize>,
 }
 
 #[derive(Debug, Clone)]
@@ -88,6 +136,20 @@ pub enum InsertEvent {
     RequestCompletion,
 }
 
+/// The last change-producing action, recorded so `.` can repeat it.
+#[derive(Debug, Clone)]
+pub(crate) enum Replay {
+    /// A normal-mode key sequence (operator + motion + count) that mutated
+    /// the document without ever entering insert mode, e.g. `dw` or `>>`.
+    Typed {
+        keys: Vec<KeyEvent>,
+        count: Option<NonZeroUsize>,
+        register: Option<char>,
+    },
+    /// A completed insert session, i.e. whatever `last_insert` holds.
+    Insert(commands::MappableCommand, Vec<InsertEvent>),
+}
+
 impl Default for EditorView {
     fn default() -> Self {
         Self::new(Keymaps::default())
@@ -104,6 +166,39 @@ impl EditorView {
             completion: None,
             spinners: ProgressSpinners::default(),
             sticky_nodes: None,
+            sticky_context_cache: StickyContextCache::default(),
+            bufferline_areas: Vec::new(),
+            bufferline_offset: 0,
+            collapsed_context_lines: BTreeSet::new(),
+            last_change: None,
+            change_keys: Vec::new(),
+            gutter_drag_anchor: None,
+            pending_counts: Vec::new(),
+        }
+    }
+
+    /// Build a linewise selection spanning full lines `anchor_line` through
+    /// `current_line` (inclusive, in whichever order they come in), trailing
+    /// newline included, oriented so the cursor lands on the `current_line`
+    /// end.
+    fn gutter_linewise_selection(
+        text: helix_core::RopeSlice,
+        anchor_line: usize,
+        current_line: usize,
+    ) -> Selection {
+        let (first, last) = if anchor_line <= current_line {
+            (anchor_line, current_line)
+        } else {
+            (current_line, anchor_line)
+        };
+
+        let start = text.line_to_char(first);
+        let end = text.line_to_char((last + 1).min(text.len_lines()));
+
+        if anchor_line <= current_line {
+            Selection::single(start, end)
+        } else {
+            Selection::single(end, start)
         }
     }
 
@@ -111,6 +206,26 @@ impl EditorView {
         &mut self.spinners
     }
 
+    /// Returns the doc line a sticky context row under the given screen
+    /// coordinates corresponds to, if any. Mirrors the row layout used by
+    /// `render_sticky_context`: each non-indicator node occupies a single
+    /// row starting at the top of the view's inner area.
+    fn sticky_context_line_at(&self, doc: &Document, view: &View, row: u16, column: u16) -> Option<usize> {
+        let context = self.sticky_nodes.as_ref()?;
+        let viewport = view.inner_area(doc);
+
+        if column < viewport.x || column >= viewport.x + viewport.width {
+            return None;
+        }
+
+        context.iter().find_map(|node| {
+            if node.indicator.is_some() {
+                return None;
+            }
+            (viewport.y + node.visual_line == row).then_some(node.line)
+        })
+    }
+
     pub fn render_view(
         &mut self,
         editor: &Editor,
@@ -125,7 +240,7 @@ impl EditorView {
         let theme = &editor.theme;
         let config = editor.config();
 
-        let text_annotations = view.text_annotations(doc, Some(theme));
+        let mut text_annotations = view.text_annotations(doc, Some(theme));
         let mut line_decorations: Vec<Box<dyn LineDecoration>> = Vec::new();
         let mut translated_positions: Vec<TranslatedPosition> = Vec::new();
 
@@ -210,7 +325,23 @@ impl EditorView {
 
         if config.sticky_context.enable {
             self.sticky_nodes =
-                Self::calculate_sticky_nodes(&self.sticky_nodes, doc, view, &config);
+                Self::calculate_sticky_nodes(
+                    &mut self.sticky_context_cache,
+                    doc,
+                    view,
+                    &config,
+                    &self.collapsed_context_lines,
+                );
+        }
+
+        if config.inline_diagnostics.enable {
+            Self::push_inline_diagnostics_annotations(
+                doc,
+                view,
+                theme,
+                &config,
+                &mut text_annotations,
+            );
         }
 
         Self::render_gutter(
@@ -245,6 +376,7 @@ impl EditorView {
                 &mut line_decorations,
                 &mut translated_positions,
                 theme,
+                &config,
             );
         }
 
@@ -439,6 +571,108 @@ impl EditorView {
         [default_vec, info_vec, hint_vec, warning_vec, error_vec]
     }
 
+    /// Append the highest-severity diagnostic on each visible line as an
+    /// end-of-line inline annotation ("error lens" style), so messages are
+    /// visible without hovering. Unlike a `LineDecoration`, inline
+    /// annotations are part of the document's text layout, so they reflow
+    /// with softwrap and scroll horizontally with the rest of the line
+    /// instead of being painted over whatever is already there.
+    pub fn push_inline_diagnostics_annotations<'doc>(
+        doc: &'doc Document,
+        view: &View,
+        theme: &Theme,
+        config: &helix_view::editor::Config,
+        text_annotations: &mut TextAnnotations<'doc>,
+    ) {
+        use helix_core::diagnostic::Severity;
+        use helix_core::line_ending::line_end_char_index;
+        use helix_core::text_annotations::InlineAnnotation;
+
+        let text = doc.text().slice(..);
+        let cursor_line = doc.selection(view.id).primary().cursor_line(text);
+        let inline_config = config.inline_diagnostics.clone();
+
+        // Bucket the diagnostics per doc line, keeping the highest-severity
+        // message and the total count, same idea as `doc_diagnostics_highlights`
+        // but per-line rather than per-scope. Multiple diagnostics on one
+        // line collapse down to the worst one plus a `(+N)` counter.
+        let mut by_line: std::collections::BTreeMap<usize, (Severity, &str, usize)> =
+            std::collections::BTreeMap::new();
+        for diagnostic in doc.diagnostics() {
+            let severity = diagnostic.severity.unwrap_or(Severity::Warning);
+            if severity < inline_config.min_severity {
+                continue;
+            }
+
+            // `Diagnostic::range` is char-indexed, same as everywhere else
+            // in this file (see `doc_diagnostics_highlights`).
+            let line = text.char_to_line(diagnostic.range.start.min(text.len_chars()));
+            by_line
+                .entry(line)
+                .and_modify(|(existing, message, count)| {
+                    *count += 1;
+                    if severity > *existing {
+                        *existing = severity;
+                        *message = diagnostic.message.as_str();
+                    }
+                })
+                .or_insert((severity, diagnostic.message.as_str(), 1));
+        }
+
+        // group by severity so each group can be added to `text_annotations`
+        // with its own highlight
+        let mut by_severity: std::collections::BTreeMap<Severity, Vec<InlineAnnotation>> =
+            std::collections::BTreeMap::new();
+
+        for (line, (severity, message, count)) in by_line {
+            // `cursor_line_only` restricts display to just the cursor line;
+            // `suppress_on_cursor_line` does the opposite, hiding it there
+            // because `render_diagnostics` already shows the diagnostics
+            // under the cursor. Both are legitimate, independent toggles.
+            if inline_config.cursor_line_only && line != cursor_line {
+                continue;
+            }
+            if inline_config.suppress_on_cursor_line && line == cursor_line {
+                continue;
+            }
+
+            let suffix = if count > 1 {
+                format!(" (+{})", count - 1)
+            } else {
+                String::new()
+            };
+
+            // truncate to fit the configured cap
+            let available = inline_config.max_message_len;
+            let message: String = message
+                .chars()
+                .take(available.saturating_sub(suffix.chars().count() + 1))
+                .collect();
+            let rendered = format!(" {message}{suffix}");
+
+            let char_idx = line_end_char_index(&text, line);
+            by_severity
+                .entry(severity)
+                .or_default()
+                .push(InlineAnnotation::new(char_idx, rendered));
+        }
+
+        for (severity, annotations) in by_severity {
+            let scope = match severity {
+                Severity::Error => "diagnostic.error",
+                Severity::Warning => "diagnostic.warning",
+                Severity::Info => "diagnostic.info",
+                Severity::Hint => "diagnostic.hint",
+            };
+            let highlight = theme
+                .find_scope_index(scope)
+                .or_else(|| theme.find_scope_index("diagnostic"))
+                .map(Highlight);
+
+            text_annotations.add_inline_annotations(annotations.into(), highlight);
+        }
+    }
+
     /// Get highlight spans for selections in a document view.
     pub fn doc_selection_highlights(
         mode: Mode,
@@ -565,7 +799,9 @@ impl EditorView {
     }
 
     /// Render bufferline at the top
-    pub fn render_bufferline(editor: &Editor, viewport: Rect, surface: &mut Surface) {
+    pub fn render_bufferline(&mut self, editor: &Editor, viewport: Rect, surface: &mut Surface) {
+        use helix_core::diagnostic::Severity;
+
         let scratch = PathBuf::from(SCRATCH_BUFFER_NAME); // default filename to use for scratch buffer
         surface.clear_with(
             viewport,
@@ -585,36 +821,154 @@ impl EditorView {
             .try_get("ui.bufferline")
             .unwrap_or_else(|| editor.theme.get("ui.statusline.inactive"));
 
-        let mut x = viewport.x;
+        let error_style = editor.theme.get("diagnostic.error");
+        let warning_style = editor.theme.get("diagnostic.warning");
+        let arrow_style = editor
+            .theme
+            .try_get("ui.bufferline")
+            .unwrap_or_else(|| editor.theme.get("ui.statusline"));
+
         let current_doc = view!(editor).doc;
 
-        for doc in editor.documents() {
-            let fname = doc
-                .path()
-                .unwrap_or(&scratch)
-                .file_name()
-                .unwrap_or_default()
-                .to_str()
-                .unwrap_or_default();
+        struct Tab {
+            id: helix_view::DocumentId,
+            text: String,
+            errors: usize,
+            warnings: usize,
+            width: u16,
+        }
+
+        // Lay every tab out first so the total width is known up front and
+        // we can work out how much needs to scroll off the left to keep the
+        // active tab on screen.
+        let tabs: Vec<Tab> = editor
+            .documents()
+            .map(|doc| {
+                let fname = doc
+                    .path()
+                    .unwrap_or(&scratch)
+                    .file_name()
+                    .unwrap_or_default()
+                    .to_str()
+                    .unwrap_or_default();
+
+                let text = format!(" {}{} ", fname, if doc.is_modified() { "[+]" } else { "" });
+
+                let (errors, warnings) =
+                    doc.diagnostics()
+                        .fold((0usize, 0usize), |(errors, warnings), diagnostic| {
+                            match diagnostic.severity {
+                                Some(Severity::Error) => (errors + 1, warnings),
+                                Some(Severity::Warning) | None => (errors, warnings + 1),
+                                _ => (errors, warnings),
+                            }
+                        });
+
+                let badge = Self::bufferline_badge_text(errors, warnings);
+                let width = text.chars().count() as u16 + badge.chars().count() as u16;
+
+                Tab {
+                    id: doc.id(),
+                    text,
+                    errors,
+                    warnings,
+                    width,
+                }
+            })
+            .collect();
+
+        let total_width: u16 = tabs.iter().map(|tab| tab.width).sum();
+
+        let mut starts = Vec::with_capacity(tabs.len());
+        let mut acc = 0u16;
+        for tab in &tabs {
+            starts.push(acc);
+            acc += tab.width;
+        }
+
+        let active_index = tabs
+            .iter()
+            .position(|tab| tab.id == current_doc)
+            .unwrap_or(0);
+        let active_start = starts.get(active_index).copied().unwrap_or(0);
+        let active_end = active_start + tabs.get(active_index).map_or(0, |tab| tab.width);
+
+        // Reserve a column on each side for the overflow arrows once the
+        // tabs stop fitting.
+        let overflows = total_width > viewport.width;
+        let arrow_width = if overflows { 1 } else { 0 };
+        let inner_width = viewport.width.saturating_sub(arrow_width * 2);
+
+        let mut offset = self.bufferline_offset;
+        if active_end.saturating_sub(offset) > inner_width {
+            offset = active_end.saturating_sub(inner_width);
+        }
+        if active_start < offset {
+            offset = active_start;
+        }
+        offset = offset.min(total_width.saturating_sub(inner_width.min(total_width)));
+        self.bufferline_offset = offset;
+
+        self.bufferline_areas.clear();
 
-            let style = if current_doc == doc.id() {
+        let mut x = viewport.x + arrow_width;
+        let clip_x = viewport.x + arrow_width + inner_width;
+
+        for (tab, start) in tabs.iter().zip(starts.iter()) {
+            let (start, end) = (*start, *start + tab.width);
+            if end <= offset || start.saturating_sub(offset) >= inner_width {
+                continue;
+            }
+
+            let style = if tab.id == current_doc {
                 bufferline_active
             } else {
                 bufferline_inactive
             };
 
-            let text = format!(" {}{} ", fname, if doc.is_modified() { "[+]" } else { "" });
-            let used_width = viewport.x.saturating_sub(x);
-            let rem_width = surface.area.width.saturating_sub(used_width);
-
+            let tab_start = x;
+            let rem_width = clip_x.saturating_sub(x);
             x = surface
-                .set_stringn(x, viewport.y, text, rem_width as usize, style)
+                .set_stringn(x, viewport.y, &tab.text, rem_width as usize, style)
                 .0;
 
-            if x >= surface.area.right() {
+            if x < clip_x && (tab.errors > 0 || tab.warnings > 0) {
+                let badge = Self::bufferline_badge_text(tab.errors, tab.warnings);
+                let badge_style = if tab.errors > 0 {
+                    error_style
+                } else {
+                    warning_style
+                };
+                let rem_width = clip_x.saturating_sub(x);
+                x = surface
+                    .set_stringn(x, viewport.y, &badge, rem_width as usize, badge_style)
+                    .0;
+            }
+
+            self.bufferline_areas.push((tab_start..x, tab.id));
+
+            if x >= clip_x {
                 break;
             }
         }
+
+        if overflows {
+            if offset > 0 {
+                surface.set_stringn(viewport.x, viewport.y, "◀", 1, arrow_style);
+            }
+            if offset + inner_width < total_width {
+                surface.set_stringn(clip_x, viewport.y, "▶", 1, arrow_style);
+            }
+        }
+    }
+
+    fn bufferline_badge_text(errors: usize, warnings: usize) -> String {
+        match (errors, warnings) {
+            (0, 0) => String::new(),
+            (0, warnings) => format!(" {warnings}"),
+            (errors, 0) => format!(" {errors}"),
+            (errors, warnings) => format!(" {errors} {warnings}"),
+        }
     }
 
     pub fn render_gutter<'d>(
@@ -633,6 +987,18 @@ impl EditorView {
             .map(|range| range.cursor_line(text))
             .collect();
 
+        // The visual (on-screen) row each cursor actually sits on, so wrapped
+        // continuation rows can be highlighted precisely instead of every
+        // visual row belonging to the doc line the cursor is on.
+        let cursor_visual_lines: Rc<[_]> = doc
+            .selection(view.id)
+            .iter()
+            .filter_map(|range| {
+                view.screen_coords_at_pos(doc, text, range.cursor(text))
+                    .map(|pos| pos.row as u16)
+            })
+            .collect();
+
         let mut offset = 0;
         let viewport = view.area;
 
@@ -641,15 +1007,23 @@ impl EditorView {
         let gutter_style_virtual = theme.get("ui.gutter.virtual");
         let gutter_selected_style_virtual = theme.get("ui.gutter.selected.virtual");
 
+        let wrap_indicator = editor.config().gutters.line_numbers.wrap_indicator.clone();
+
         for gutter_type in view.gutters() {
+            let is_line_numbers = matches!(gutter_type, helix_view::editor::GutterType::LineNumbers);
             let mut gutter = gutter_type.style(editor, doc, view, theme, is_focused);
             let width = gutter_type.width(view, doc);
             // avoid lots of small allocations by reusing a text buffer for each line
             let mut text_to_draw = String::with_capacity(width);
             let cursors = cursors.clone();
+            let cursor_visual_lines = cursor_visual_lines.clone();
+            let wrap_indicator = wrap_indicator.clone();
             let gutter_decoration = move |renderer: &mut TextRenderer, pos: LinePos| {
-                // TODO handle softwrap in gutters
-                let selected = cursors.contains(&pos.doc_line);
+                let selected = if pos.first_visual_line {
+                    cursors.contains(&pos.doc_line)
+                } else {
+                    cursor_visual_lines.contains(&pos.visual_line)
+                };
                 let x = viewport.x + offset;
                 let y = viewport.y + pos.visual_line;
 
@@ -671,6 +1045,23 @@ impl EditorView {
                     };
                 }
 
+                // softwrapped continuation rows of the line-number gutter get
+                // a configurable indicator glyph instead of a blank/repeated
+                // number.
+                if is_line_numbers && !pos.first_visual_line {
+                    if let Some(indicator) = wrap_indicator.as_deref() {
+                        renderer.surface.set_stringn(
+                            x,
+                            y,
+                            indicator,
+                            width,
+                            gutter_style,
+                        );
+                        text_to_draw.clear();
+                        return;
+                    }
+                }
+
                 if let Some(style) =
                     gutter(doc_line, selected, pos.first_visual_line, &mut text_to_draw)
                 {
@@ -773,6 +1164,7 @@ impl EditorView {
         line_decoration: &mut [Box<dyn LineDecoration + '_>],
         translated_positions: &mut [TranslatedPosition],
         theme: &Theme,
+        config: &helix_view::editor::Config,
     ) {
         let Some(context) = context else {
             return;
@@ -781,6 +1173,14 @@ impl EditorView {
         let text = doc.text().slice(..);
         let viewport = view.inner_area(doc);
 
+        // keep the sticky header in lockstep with the buffer's horizontal
+        // scroll by default, but let it stay pinned to column 0 if disabled
+        let horizontal_offset = if config.sticky_context.follow_horizontal_scroll {
+            view.offset.horizontal_offset
+        } else {
+            0
+        };
+
         // backup (status line) shall always exist
         let status_line_style = theme
             .try_get("ui.statusline")
@@ -796,6 +1196,11 @@ impl EditorView {
 
         let mut context_area = viewport;
         context_area.height = 1;
+        if let Some(max_width) = config.sticky_context.max_width {
+            context_area.width = context_area.width.min(max_width);
+        }
+
+        const MORE: &str = "…";
 
         for node in context {
             surface.clear_with(context_area, context_style);
@@ -849,13 +1254,8 @@ impl EditorView {
                     (already_written + dots.len() as u16).saturating_sub(overdraw_offset as u16);
 
                 // render the end of the function definition
-                let mut renderer = TextRenderer::new(
-                    surface,
-                    doc,
-                    theme,
-                    view.offset.horizontal_offset,
-                    additional_area,
-                );
+                let mut renderer =
+                    TextRenderer::new(surface, doc, theme, horizontal_offset, additional_area);
                 new_offset.anchor = text.byte_to_char(node.byte_range.end);
                 let highlights = Self::doc_syntax_highlights(doc, new_offset.anchor, 1, theme);
 
@@ -892,16 +1292,13 @@ impl EditorView {
             // get all highlights from the latest point
             let highlights = Self::doc_syntax_highlights(doc, new_offset.anchor, 1, theme);
 
-            let mut renderer = TextRenderer::new(
-                surface,
-                doc,
-                theme,
-                view.offset.horizontal_offset,
-                line_context_area,
-            );
+            let mut renderer =
+                TextRenderer::new(surface, doc, theme, horizontal_offset, line_context_area);
 
             // limit the width to its size - 1, so that it won't draw trailing whitespace characters
-            line_context_area.width = already_written - 1;
+            let full_width = already_written - 1;
+            let overflows = full_width > context_area.width;
+            line_context_area.width = full_width.min(context_area.width);
 
             render_text(
                 &mut renderer,
@@ -915,6 +1312,19 @@ impl EditorView {
                 translated_positions,
             );
 
+            // the line is wider than the context area (the viewport, or
+            // `max_width` when set), so fade the right edge with an
+            // indicator rather than silently truncating it
+            if overflows {
+                surface.set_stringn(
+                    context_area.right().saturating_sub(1),
+                    line_context_area.y,
+                    MORE,
+                    MORE.len(),
+                    indicator_style,
+                );
+            }
+
             // next node
             context_area.y += 1;
         }
@@ -952,15 +1362,88 @@ impl EditorView {
             .next()
     }
 
-    /// Calculates the sticky nodes
+    /// Width, in columns, of a line's leading indentation, honoring
+    /// `tab_width` for tabs. Returns `None` for a blank (whitespace-only)
+    /// line.
+    fn indent_width(line: RopeSlice, tab_width: usize) -> Option<usize> {
+        let mut width = 0;
+        for ch in line.chars() {
+            match ch {
+                ' ' => width += 1,
+                '\t' => width += tab_width - (width % tab_width),
+                c if c.is_whitespace() => break,
+                _ => return Some(width),
+            }
+        }
+        None
+    }
+
+    /// Synthesizes `StickyNode`s from indentation for languages with no
+    /// tree-sitter context query: walking upward from `anchor_line`, keep
+    /// the most recent less-indented non-blank line at each decreasing
+    /// indent level, so the sticky area shows the enclosing headers the
+    /// same way a query-driven one would.
+    fn indentation_sticky_nodes(
+        text: RopeSlice,
+        anchor_line: usize,
+        tab_width: usize,
+        anchor: usize,
+    ) -> Vec<StickyNode> {
+        let mut result = Vec::new();
+
+        let Some(mut current_indent) = text
+            .get_line(anchor_line)
+            .and_then(|l| Self::indent_width(l, tab_width))
+        else {
+            return result;
+        };
+
+        let mut line = anchor_line;
+        while line > 0 && current_indent > 0 {
+            line -= 1;
+            let Some(this_line) = text.get_line(line) else {
+                continue;
+            };
+            let Some(width) = Self::indent_width(this_line, tab_width) else {
+                continue;
+            };
+            if width >= current_indent {
+                continue;
+            }
+
+            current_indent = width;
+
+            let leading_whitespace = this_line
+                .chars()
+                .take_while(|c| *c == ' ' || *c == '\t')
+                .count();
+            let header_start = text.line_to_char(line) + leading_whitespace;
+            let header_byte = text.char_to_byte(header_start);
+
+            result.push(StickyNode {
+                line,
+                visual_line: 0,
+                byte_range: header_byte..header_byte,
+                indicator: None,
+                anchor,
+                has_context_end: false,
+            });
+        }
+
+        result.reverse();
+        result
+    }
+
+    /// Calculates the sticky nodes, reusing `cache` across frames so an
+    /// ordinary scroll only re-queries the bytes the anchor moved through
+    /// rather than rescanning the whole document every time.
     fn calculate_sticky_nodes(
-        nodes: &Option<Vec<StickyNode>>,
+        cache: &mut StickyContextCache,
         doc: &Document,
         view: &View,
         config: &helix_view::editor::Config,
+        collapsed: &BTreeSet<usize>,
     ) -> Option<Vec<StickyNode>> {
-        let syntax = doc.syntax()?;
-        let tree = syntax.tree();
         let text = doc.text().slice(..);
         let viewport = view.inner_area(doc);
         let cursor_byte = text.char_to_byte(doc.selection(view.id).primary().cursor(text));
@@ -976,8 +1459,15 @@ impl EditorView {
 
         let anchor_line = text.char_to_line(view.offset.anchor);
 
-        let top_first_byte =
-            text.line_to_byte(anchor_line + nodes.as_ref().map_or(0, |nodes| nodes.len()));
+        let revision = doc.get_current_revision();
+        let edited = revision != cache.revision;
+        if edited {
+            // the document changed since the cache was built: nothing in it
+            // can be trusted, so rebuild from scratch
+            cache.nodes.clear();
+        }
+
+        let top_first_byte = text.line_to_byte(anchor_line + cache.nodes.len());
 
         let last_scan_byte = if config.sticky_context.follow_cursor {
             cursor_byte
@@ -985,73 +1475,132 @@ impl EditorView {
             top_first_byte
         };
 
-        // nothing has changed, so the cached result can be returned
-        if let Some(nodes) = nodes {
-            if nodes.iter().any(|node| view.offset.anchor == node.anchor) {
-                return Some(
-                    nodes
-                        .iter()
-                        .take(visual_cursor_pos as usize)
-                        .map(|elem| elem.clone())
-                        .collect(),
-                );
-            }
+        // nothing has changed, so the cached result can be returned as is
+        if !edited && anchor_line == cache.anchor_line && !cache.nodes.is_empty() {
+            return Some(
+                cache
+                    .nodes
+                    .iter()
+                    .filter(|node| !collapsed.contains(&node.line))
+                    .take(visual_cursor_pos as usize)
+                    .cloned()
+                    .collect(),
+            );
         }
 
-        let context_nodes = doc
-            .language_config()
-            .and_then(|lang| lang.context_query())?;
-
-        let start_index = context_nodes.query.capture_index_for_name("context")?;
-        let end_index = context_nodes
-            .query
-            .capture_index_for_name("context.end")
-            .unwrap_or(start_index);
-
-        // context is list of numbers of lines that should be rendered in the LSP context
-        let mut context: BTreeSet<StickyNode> = BTreeSet::new();
-
-        let mut cursor = QueryCursor::new();
-
-        // only run the query from start to the cursor location
-        cursor.set_byte_range(0..last_scan_byte);
-        let query = &context_nodes.query;
-        let query_nodes = cursor.matches(query, tree.root_node(), RopeProvider(text));
-
-        for matched_node in query_nodes {
-            // find @context.end nodes
-            let node_byte_range = Self::get_context_paired_range(
-                &matched_node,
-                start_index,
-                end_index,
-                top_first_byte,
-                last_scan_byte,
-            );
+        let scroll_delta = anchor_line.abs_diff(cache.anchor_line);
+        let incremental = !edited
+            && !cache.nodes.is_empty()
+            && scroll_delta <= STICKY_CONTEXT_INCREMENTAL_SCROLL_THRESHOLD;
+
+        // on an incremental update, seed the candidate set with whatever
+        // cached nodes still plausibly enclose the new anchor; the query
+        // below only has to find what's new, and the sort/dedup/cap
+        // pipeline further down cleans up anything that's left stale
+        let mut context: BTreeSet<StickyNode> = if incremental {
+            cache
+                .nodes
+                .iter()
+                .filter(|node| node.indicator.is_none() && node.line <= anchor_line)
+                .cloned()
+                .collect()
+        } else {
+            BTreeSet::new()
+        };
 
-            for node in matched_node.nodes_for_capture_index(start_index) {
-                if (!node.byte_range().contains(&last_scan_byte)
-                    || !node.byte_range().contains(&top_first_byte))
-                    && node.start_position().row != anchor_line + context.len()
-                    && node_byte_range.is_none()
-                {
-                    continue;
-                }
+        // a full rescan starts from byte 0 like before; an incremental
+        // update only re-queries the span the anchor moved through
+        let scan_from = if incremental {
+            cache.last_scan_byte.min(last_scan_byte)
+        } else {
+            0
+        };
 
-                context.insert(StickyNode {
-                    line: node.start_position().row,
-                    visual_line: 0,
-                    byte_range: node_byte_range
-                        .as_ref()
-                        .unwrap_or(&(node.start_byte()..node.start_byte()))
-                        .clone(),
-                    indicator: None,
-                    anchor: view.offset.anchor,
-                    has_context_end: node_byte_range.is_some(),
-                });
+        if let Some(syntax) = doc.syntax() {
+            if let Some(context_nodes) = doc.language_config().and_then(|lang| lang.context_query())
+            {
+                let tree = syntax.tree();
+
+                let start_index = context_nodes.query.capture_index_for_name("context");
+                let end_index = context_nodes
+                    .query
+                    .capture_index_for_name("context.end")
+                    .or(start_index);
+
+                if let (Some(start_index), Some(end_index)) = (start_index, end_index) {
+                    let mut cursor = QueryCursor::new();
+
+                    // only run the query over the (possibly narrow) byte
+                    // range that needs re-scanning
+                    cursor.set_byte_range(scan_from..last_scan_byte);
+                    let query = &context_nodes.query;
+                    let query_nodes = cursor.matches(query, tree.root_node(), RopeProvider(text));
+
+                    for matched_node in query_nodes {
+                        // find @context.end nodes
+                        let node_byte_range = Self::get_context_paired_range(
+                            &matched_node,
+                            start_index,
+                            end_index,
+                            top_first_byte,
+                            last_scan_byte,
+                        );
+
+                        for node in matched_node.nodes_for_capture_index(start_index) {
+                            if (!node.byte_range().contains(&last_scan_byte)
+                                || !node.byte_range().contains(&top_first_byte))
+                                && node.start_position().row != anchor_line + context.len()
+                                && node_byte_range.is_none()
+                            {
+                                continue;
+                            }
+
+                            context.insert(StickyNode {
+                                line: node.start_position().row,
+                                visual_line: 0,
+                                byte_range: node_byte_range
+                                    .as_ref()
+                                    .unwrap_or(&(node.start_byte()..node.start_byte()))
+                                    .clone(),
+                                indicator: None,
+                                anchor: view.offset.anchor,
+                                has_context_end: node_byte_range.is_some(),
+                            });
+                        }
+                    }
+                }
             }
         }
-        // context should be filled by now
+
+        // no tree-sitter context query available (or it matched nothing, e.g.
+        // plain text/config/YAML-ish files): synthesize sticky headers from
+        // indentation instead of showing nothing
         if context.is_empty() {
+            context.extend(Self::indentation_sticky_nodes(
+                text,
+                anchor_line,
+                doc.tab_width(),
+                view.offset.anchor,
+            ));
+        }
+
+        // an incremental merge can leave cached nodes that no longer
+        // enclose the new anchor; drop them before they're cached back or
+        // capped
+        let mut nodes: Vec<StickyNode> = context
+            .into_iter()
+            .filter(|node| node.line <= anchor_line)
+            .collect();
+        nodes.sort_unstable_by(|lhs, rhs| lhs.line.cmp(&rhs.line));
+        nodes.dedup_by(|lhs, rhs| lhs.line == rhs.line);
+
+        cache.nodes = nodes.clone();
+        cache.anchor_line = anchor_line;
+        cache.last_scan_byte = last_scan_byte;
+        cache.revision = revision;
+
+        // context should be filled by now
+        if nodes.is_empty() {
             return None;
         }
 
@@ -1064,8 +1613,11 @@ impl EditorView {
             max_lines.min(viewport.height) as usize
         };
 
-        let mut result: Vec<_> = context
+        let mut result: Vec<_> = nodes
             .iter()
+            // a right click on a sticky row collapses that scope; skip it
+            // entirely rather than showing a stale/irrelevant header
+            .filter(|node| !collapsed.contains(&node.line))
             // only take the nodes until 1 / 3 of the viewport is reached or the maximum amount of sticky nodes
             .take(max_nodes_amount)
             .enumerate()
@@ -1093,6 +1645,7 @@ impl EditorView {
             });
         }
 
+        cache.nodes = result.clone();
         Some(result)
     }
 
@@ -1222,6 +1775,14 @@ impl EditorView {
                     self.clear_completion(cxt.editor);
                     cxt.editor.completion_request_handle = None;
 
+                    // the insert session that just committed is the most
+                    // recent change; record it so `.` repeats it even if an
+                    // older normal-mode edit is still cached.
+                    self.last_change = Some(Replay::Insert(
+                        self.last_insert.0.clone(),
+                        self.last_insert.1.clone(),
+                    ));
+
                     // TODO: Use an on_mode_change hook to remove signature help
                     cxt.jobs.callback(async {
                         let call: job::Callback =
@@ -1288,53 +1849,50 @@ impl EditorView {
             }
             // special handling for repeat operator
             (key!('.'), _) if self.keymaps.pending().is_empty() => {
-                for _ in 0..cxt.editor.count.map_or(1, NonZeroUsize::into) {
-                    // first execute whatever put us into insert mode
-                    self.last_insert.0.execute(cxt);
-                    let mut last_savepoint = None;
-                    let mut last_request_savepoint = None;
-                    // then replay the inputs
-                    for key in self.last_insert.1.clone() {
-                        match key {
-                            InsertEvent::Key(key) => self.insert_mode(cxt, key),
-                            InsertEvent::CompletionApply {
-                                trigger_offset,
-                                changes,
-                            } => {
-                                let (view, doc) = current!(cxt.editor);
-
-                                if let Some(last_savepoint) = last_savepoint.as_deref() {
-                                    doc.restore(view, last_savepoint, true);
-                                }
-
-                                let text = doc.text().slice(..);
-                                let cursor = doc.selection(view.id).primary().cursor(text);
-
-                                let shift_position = |pos: usize| -> usize {
-                                    (pos + cursor).saturating_sub(trigger_offset)
-                                };
-
-                                let tx = Transaction::change(
-                                    doc.text(),
-                                    changes.iter().cloned().map(|(start, end, t)| {
-                                        (shift_position(start), shift_position(end), t)
-                                    }),
-                                );
-                                doc.apply(&tx, view.id);
-                            }
-                            InsertEvent::TriggerCompletion => {
-                                last_savepoint = take(&mut last_request_savepoint);
-                            }
-                            InsertEvent::RequestCompletion => {
-                                let (view, doc) = current!(cxt.editor);
-                                last_request_savepoint = Some(doc.savepoint(view));
-                            }
+                let repeat_count = cxt.editor.count;
+                match self.last_change.clone() {
+                    // a pure normal-mode edit (e.g. `dw`, `x`, `rX`, `>>`,
+                    // paste, `J`) that never touched insert mode: replay the
+                    // exact key sequence that produced it.
+                    Some(Replay::Typed {
+                        keys,
+                        count: recorded_count,
+                        register,
+                    }) => {
+                        // a count given to `.` itself replaces the recorded
+                        // one, it does not multiply with it
+                        cxt.editor.count = repeat_count.or(recorded_count);
+                        cxt.editor.selected_register = register;
+                        for key in keys {
+                            self.command_mode(mode, cxt, key);
                         }
+                        cxt.editor.count = None;
+                    }
+                    // the most recent change was an insert session
+                    Some(Replay::Insert(command, events)) => {
+                        self.replay_insert(cxt, command, events, repeat_count);
+                    }
+                    // nothing recorded yet: fall back to whatever
+                    // `last_insert` holds (a no-op by default)
+                    None => {
+                        let command = self.last_insert.0.clone();
+                        let events = self.last_insert.1.clone();
+                        self.replay_insert(cxt, command, events, repeat_count);
                     }
                 }
-                cxt.editor.count = None;
             }
             _ => {
+                // if we're continuing an operator/prefix sequence that
+                // stashed an outer count, fold it into whatever count this
+                // key's own digits built up, so e.g. `2d3w` deletes
+                // 2 * 3 = 6 words
+                if !self.keymaps.pending().is_empty() {
+                    if let Some(outer) = self.pending_counts.pop() {
+                        let inner = cxt.editor.count.unwrap_or(NonZeroUsize::new(1).unwrap());
+                        cxt.editor.count = NonZeroUsize::new(outer.get().saturating_mul(inner.get()));
+                    }
+                }
+
                 // set the count
                 cxt.count = cxt.editor.count;
                 // TODO: edge case: 0j -> reset to 1
@@ -1344,9 +1902,43 @@ impl EditorView {
                 // set the register
                 cxt.register = cxt.editor.selected_register.take();
 
+                // accumulate this key in case it's (part of) a
+                // document-mutating normal-mode command that `.` should be
+                // able to replay later
+                self.change_keys.push(event);
+                let revision_before = doc!(cxt.editor).get_current_revision();
+
+                let was_pending = !self.keymaps.pending().is_empty();
                 self.handle_keymap_event(mode, cxt, event);
-                if self.keymaps.pending().is_empty() {
-                    cxt.editor.count = None
+                let now_pending = !self.keymaps.pending().is_empty();
+
+                if !was_pending && now_pending {
+                    // this key started a new operator/prefix sequence:
+                    // stash whatever count preceded it (default 1) so the
+                    // motion that eventually completes the sequence can
+                    // multiply its own count into it
+                    self.pending_counts.push(
+                        cxt.editor
+                            .count
+                            .take()
+                            .unwrap_or(NonZeroUsize::new(1).unwrap()),
+                    );
+                }
+
+                if !now_pending {
+                    cxt.editor.count = None;
+
+                    let revision_after = doc!(cxt.editor).get_current_revision();
+                    let mutated = revision_after != revision_before;
+                    if cxt.editor.mode() == Mode::Normal && mutated {
+                        self.last_change = Some(Replay::Typed {
+                            keys: take(&mut self.change_keys),
+                            count: cxt.count,
+                            register: cxt.register,
+                        });
+                    } else {
+                        self.change_keys.clear();
+                    }
                 } else {
                     cxt.editor.selected_register = cxt.register.take();
                 }
@@ -1354,6 +1946,62 @@ impl EditorView {
         }
     }
 
+    /// Replay a recorded insert session: execute the command that entered
+    /// insert mode, then feed back every key/event it recorded.
+    fn replay_insert(
+        &mut self,
+        cxt: &mut commands::Context,
+        command: commands::MappableCommand,
+        events: Vec<InsertEvent>,
+        count: Option<NonZeroUsize>,
+    ) {
+        for _ in 0..count.map_or(1, NonZeroUsize::into) {
+            // first execute whatever put us into insert mode
+            command.execute(cxt);
+            let mut last_savepoint = None;
+            let mut last_request_savepoint = None;
+            // then replay the inputs
+            for key in events.clone() {
+                match key {
+                    InsertEvent::Key(key) => self.insert_mode(cxt, key),
+                    InsertEvent::CompletionApply {
+                        trigger_offset,
+                        changes,
+                    } => {
+                        let (view, doc) = current!(cxt.editor);
+
+                        if let Some(last_savepoint) = last_savepoint.as_deref() {
+                            doc.restore(view, last_savepoint, true);
+                        }
+
+                        let text = doc.text().slice(..);
+                        let cursor = doc.selection(view.id).primary().cursor(text);
+
+                        let shift_position = |pos: usize| -> usize {
+                            (pos + cursor).saturating_sub(trigger_offset)
+                        };
+
+                        let tx = Transaction::change(
+                            doc.text(),
+                            changes.iter().cloned().map(|(start, end, t)| {
+                                (shift_position(start), shift_position(end), t)
+                            }),
+                        );
+                        doc.apply(&tx, view.id);
+                    }
+                    InsertEvent::TriggerCompletion => {
+                        last_savepoint = take(&mut last_request_savepoint);
+                    }
+                    InsertEvent::RequestCompletion => {
+                        let (view, doc) = current!(cxt.editor);
+                        last_request_savepoint = Some(doc.savepoint(view));
+                    }
+                }
+            }
+        }
+        cxt.editor.count = None;
+    }
+
     #[allow(clippy::too_many_arguments)]
     pub fn set_completion(
         &mut self,
@@ -1464,9 +2112,39 @@ impl EditorView {
         };
 
         match kind {
+            // A secondary (right) click on a sticky context row hides that
+            // header from the context stack. A hidden header is no longer
+            // drawn, so it cannot be clicked again to bring it back.
+            MouseEventKind::Down(MouseButton::Right) => {
+                let editor = &mut cxt.editor;
+
+                if editor.config().sticky_context.enable {
+                    let (view, doc) = current!(editor);
+                    if let Some(line) = self.sticky_context_line_at(doc, view, row, column) {
+                        if !self.collapsed_context_lines.remove(&line) {
+                            self.collapsed_context_lines.insert(line);
+                        }
+                        return EventResult::Consumed(None);
+                    }
+                }
+
+                EventResult::Ignored(None)
+            }
+
             MouseEventKind::Down(MouseButton::Left) => {
                 let editor = &mut cxt.editor;
 
+                if editor.config().sticky_context.enable {
+                    let (view, doc) = current!(editor);
+                    if let Some(line) = self.sticky_context_line_at(doc, view, row, column) {
+                        let pos = doc.text().line_to_char(line);
+                        doc.set_selection(view.id, Selection::point(pos));
+                        let view_id = view.id;
+                        editor.ensure_cursor_in_view(view_id);
+                        return EventResult::Consumed(None);
+                    }
+                }
+
                 if let Some((pos, view_id)) = pos_and_view(editor, row, column, true) {
                     let doc = doc_mut!(editor, &view!(editor, view_id).doc);
 
@@ -1488,16 +2166,17 @@ impl EditorView {
 
                     let (view, doc) = current!(cxt.editor);
 
-                    let path = match doc.path() {
-                        Some(path) => path.clone(),
-                        None => return EventResult::Ignored(None),
-                    };
-
                     if let Some(char_idx) =
                         view.pos_at_visual_coords(doc, coords.row as u16, coords.col as u16, true)
                     {
                         let line = doc.text().char_to_line(char_idx);
-                        commands::dap_toggle_breakpoint_impl(cxt, path, line);
+                        // select the clicked line immediately; if the press
+                        // turns into a drag it grows linewise, and if it
+                        // doesn't, release falls back to the breakpoint
+                        // toggle this arm used to do unconditionally
+                        self.gutter_drag_anchor = Some(line);
+                        let selection = Self::gutter_linewise_selection(doc.text().slice(..), line, line);
+                        doc.set_selection(view.id, selection);
                         return EventResult::Consumed(None);
                     }
                 }
@@ -1505,7 +2184,51 @@ impl EditorView {
                 EventResult::Ignored(None)
             }
 
+            // Side ("thumb") buttons: X11 and Windows report these as
+            // button codes 8 (back) and 9 (forward), which is how they
+            // surface here as `MouseButton::Other`. Drive the jumplist with
+            // them, the same as `Ctrl-o`/`Ctrl-i` already do.
+            MouseEventKind::Down(MouseButton::Other(code)) => {
+                let editor = &mut cxt.editor;
+                let direction = match code {
+                    8 => Direction::Backward,
+                    9 => Direction::Forward,
+                    _ => return EventResult::Ignored(None),
+                };
+
+                if let Some((_, view_id)) = pos_and_view(editor, row, column, false) {
+                    editor.focus(view_id);
+                }
+
+                match direction {
+                    Direction::Backward => commands::MappableCommand::jump_backward.execute(cxt),
+                    Direction::Forward => commands::MappableCommand::jump_forward.execute(cxt),
+                }
+
+                EventResult::Consumed(None)
+            }
+
             MouseEventKind::Drag(MouseButton::Left) => {
+                if let Some(anchor_line) = self.gutter_drag_anchor {
+                    let Some((coords, _)) = gutter_coords_and_view(cxt.editor, row, column) else {
+                        return EventResult::Ignored(None);
+                    };
+                    let (view, doc) = current!(cxt.editor);
+                    let current_line = match view
+                        .pos_at_visual_coords(doc, coords.row as u16, coords.col as u16, true)
+                    {
+                        Some(char_idx) => doc.text().char_to_line(char_idx),
+                        None => return EventResult::Ignored(None),
+                    };
+
+                    let selection =
+                        Self::gutter_linewise_selection(doc.text().slice(..), anchor_line, current_line);
+                    doc.set_selection(view.id, selection);
+                    let view_id = view.id;
+                    cxt.editor.ensure_cursor_in_view(view_id);
+                    return EventResult::Consumed(None);
+                }
+
                 let (view, doc) = current!(cxt.editor);
 
                 let pos = match view.pos_at_screen_coords(doc, row, column, true) {
@@ -1546,6 +2269,27 @@ impl EditorView {
             }
 
             MouseEventKind::Up(MouseButton::Left) => {
+                if let Some(anchor_line) = self.gutter_drag_anchor.take() {
+                    let click = gutter_coords_and_view(cxt.editor, row, column).and_then(
+                        |(coords, _)| {
+                            let (view, doc) = current!(cxt.editor);
+                            view.pos_at_visual_coords(doc, coords.row as u16, coords.col as u16, true)
+                                .map(|char_idx| (doc.text().char_to_line(char_idx), doc.path().cloned()))
+                        },
+                    );
+
+                    if let Some((current_line, Some(path))) = click {
+                        if current_line == anchor_line {
+                            // the press never turned into a drag: fall back
+                            // to the plain click behavior, toggling a
+                            // breakpoint
+                            commands::dap_toggle_breakpoint_impl(cxt, path, anchor_line);
+                        }
+                    }
+
+                    return EventResult::Consumed(None);
+                }
+
                 if !config.middle_click_paste {
                     return EventResult::Ignored(None);
                 }
@@ -1660,12 +2404,15 @@ impl Component for EditorView {
             }
             Event::Key(mut key) => {
                 cx.editor.reset_idle_timer();
-                canonicalize_key(&mut key);
+
+                // resolved per the keymap layer for the current mode, so a
+                // layer that binds distinct actions to e.g. `S-a` and `a`
+                // can opt out of the rewrite without affecting other layers
+                let mode = cx.editor.mode();
+                canonicalize_key(&mut key, self.keymaps.normalize_shift(mode));
 
                 // clear status
                 cx.editor.status_msg = None;
-
-                let mode = cx.editor.mode();
                 let (view, _) = current!(cx.editor);
                 let focus = view.id;
 
@@ -1805,7 +2552,7 @@ impl Component for EditorView {
         cx.editor.resize(editor_area);
 
         if use_bufferline {
-            Self::render_bufferline(cx.editor, area.with_height(1), surface);
+            self.render_bufferline(cx.editor, area.with_height(1), surface);
         }
 
         for (view, is_focused) in cx.editor.tree.views() {
@@ -1893,12 +2640,89 @@ impl Component for EditorView {
     }
 }
 
-fn canonicalize_key(key: &mut KeyEvent) {
-    if let KeyEvent {
-        code: KeyCode::Char(_),
-        modifiers: _,
-    } = key
-    {
-        key.modifiers.remove(KeyModifiers::SHIFT)
+/// Strip the SHIFT modifier from a `Char` key so e.g. `S-a` and `a` map to
+/// the same binding, the way most keymaps expect. Non-char keys pass through
+/// unchanged; a `Char` key that also carries other modifiers, such as
+/// `C-S-a`, keeps them and loses only the SHIFT bit (`C-a`).
+///
+/// Pure and side-effect free, so the input subsystem, macro replay and key
+/// sequence matching can all share this one canonical normalization path
+/// (and it's trivially unit-testable in isolation).
+///
+/// `normalize_shift` is resolved per call site from the keymap layer active
+/// for the current mode (`Keymaps::normalize_shift`), so a layer can opt out
+/// independently of the others: some users bind distinct actions to `S-a`
+/// vs `a`, or rely on a layout where shifted characters are semantically
+/// meaningful (this also matters on terminals using the Kitty keyboard
+/// protocol, which reports the SHIFT bit alongside the base key even for
+/// characters it already shifted). Passing `false` returns `key` unchanged.
+pub fn normalize_key_event(key: KeyEvent, normalize_shift: bool) -> KeyEvent {
+    let mut key = key;
+    if normalize_shift {
+        if let KeyEvent {
+            code: KeyCode::Char(_),
+            modifiers: _,
+        } = &key
+        {
+            key.modifiers.remove(KeyModifiers::SHIFT)
+        }
+    }
+    key
+}
+
+fn canonicalize_key(key: &mut KeyEvent, normalize_shift: bool) {
+    *key = normalize_key_event(*key, normalize_shift);
+}
+
+#[cfg(test)]
+mod normalize_key_event_tests {
+    use super::*;
+
+    #[test]
+    fn strips_shift_from_char_keys() {
+        let key = KeyEvent {
+            code: KeyCode::Char('A'),
+            modifiers: KeyModifiers::SHIFT,
+        };
+        assert_eq!(
+            normalize_key_event(key, true),
+            KeyEvent {
+                code: KeyCode::Char('A'),
+                modifiers: KeyModifiers::NONE,
+            }
+        );
+    }
+
+    #[test]
+    fn keeps_other_modifiers_alongside_shift() {
+        let key = KeyEvent {
+            code: KeyCode::Char('a'),
+            modifiers: KeyModifiers::CONTROL | KeyModifiers::SHIFT,
+        };
+        assert_eq!(
+            normalize_key_event(key, true),
+            KeyEvent {
+                code: KeyCode::Char('a'),
+                modifiers: KeyModifiers::CONTROL,
+            }
+        );
+    }
+
+    #[test]
+    fn leaves_non_char_keys_unchanged() {
+        let key = KeyEvent {
+            code: KeyCode::Enter,
+            modifiers: KeyModifiers::SHIFT,
+        };
+        assert_eq!(normalize_key_event(key, true), key);
+    }
+
+    #[test]
+    fn leaves_key_unchanged_when_disabled() {
+        let key = KeyEvent {
+            code: KeyCode::Char('A'),
+            modifiers: KeyModifiers::SHIFT,
+        };
+        assert_eq!(normalize_key_event(key, false), key);
     }
 }
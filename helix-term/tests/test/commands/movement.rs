@@ -726,3 +726,74 @@ async fn select_all_children() -> anyhow::Result<()> {
 
     Ok(())
 }
+
+#[tokio::test(flavor = "multi_thread")]
+async fn transpose_node() -> anyhow::Result<()> {
+    let tests = vec![
+        // swaps the enclosing argument with its next sibling, selection
+        // follows the relocated node
+        (
+            helpers::platform_line(indoc! {r##"
+                fn foo() {
+                    bar(#[a|]#, b, c);
+                }
+            "##}),
+            "<A-t>",
+            helpers::platform_line(indoc! {r##"
+                fn foo() {
+                    bar(b, #[a|]#, c);
+                }
+            "##}),
+        ),
+        // and with its previous sibling
+        (
+            helpers::platform_line(indoc! {r##"
+                fn foo() {
+                    bar(a, #[b|]#, c);
+                }
+            "##}),
+            "<A-T>",
+            helpers::platform_line(indoc! {r##"
+                fn foo() {
+                    bar(#[b|]#, a, c);
+                }
+            "##}),
+        ),
+        // no sibling in the requested direction - selection stays the same
+        (
+            helpers::platform_line(indoc! {r##"
+                fn foo() {
+                    bar(a, b, #[c|]#);
+                }
+            "##}),
+            "<A-t>",
+            helpers::platform_line(indoc! {r##"
+                fn foo() {
+                    bar(a, b, #[c|]#);
+                }
+            "##}),
+        ),
+        // two cursors whose swaps overlap (the one on `a` swaps with `b`,
+        // the one on `b` with `c`) only perform the first swap rather than
+        // emitting conflicting changes over the same span
+        (
+            helpers::platform_line(indoc! {r##"
+                fn foo() {
+                    bar(#[a|]#, #(b|)#, c);
+                }
+            "##}),
+            "<A-t>",
+            helpers::platform_line(indoc! {r##"
+                fn foo() {
+                    bar(b, #[a|]#, c);
+                }
+            "##}),
+        ),
+    ];
+
+    for test in tests {
+        test_with_config(AppBuilder::new().with_file("foo.rs", None), test).await?;
+    }
+
+    Ok(())
+}